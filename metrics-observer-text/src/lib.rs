@@ -44,16 +44,35 @@
 //!
 #![deny(missing_docs)]
 use hdrhistogram::Histogram;
-use metrics_core::{Builder, Drain, Key, Label, Observer};
-use metrics_util::{parse_quantiles, Quantile};
+use log::Level;
+use metrics_core::{Builder, Drain, Key, Label, Observe, Observer};
+use metrics_util::{parse_quantiles, MetricKind, MetricKindMask, Quantile, Summary};
 use std::{
     collections::{HashMap, VecDeque},
     fmt::Display,
+    thread,
+    time::{Duration, Instant},
 };
 
+/// Default set of histogram bucket upper bounds used when rendering in bucket mode.
+const DEFAULT_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Maximum number of buckets retained by a streaming [`Summary`]; mirrors its `with_defaults`.
+const SUMMARY_MAX_BUCKETS: u32 = 32_768;
+
+/// Smallest absolute value a streaming [`Summary`] distinguishes from zero; mirrors its
+/// `with_defaults`.
+const SUMMARY_MIN_VALUE: f64 = 1.0e-9;
+
 /// Builder for [`TextRecorder`].
 pub struct TextBuilder {
     quantiles: Vec<Quantile>,
+    buckets: Option<Vec<f64>>,
+    summary_error: Option<f64>,
+    idle_timeout: Option<Duration>,
+    recency_mask: MetricKindMask,
 }
 
 impl TextBuilder {
@@ -76,8 +95,68 @@ impl TextBuilder {
 
         Self {
             quantiles: actual_quantiles,
+            buckets: None,
+            summary_error: None,
+            idle_timeout: None,
+            recency_mask: MetricKindMask::NONE,
         }
     }
+
+    /// Evicts metrics whose value has not changed within the given idle timeout.
+    ///
+    /// `mask` selects which metric kinds are subject to expiry (for example
+    /// `MetricKindMask::COUNTER | MetricKindMask::GAUGE`).  On each [`Drain::drain`] call, a metric
+    /// of a masked kind whose value has not changed for `timeout` or longer is omitted from the
+    /// rendered snapshot and dropped from internal state, so long-lived snapshots don't accumulate
+    /// dead entries for transient labeled series.  Expiry keys off the last time a value changed,
+    /// not the last time it was observed — a series re-reported every cycle with the same value is
+    /// still aged out.
+    ///
+    /// Only counters and gauges are tracked for recency; histograms are fully drained on every
+    /// cycle and so never age out, meaning `MetricKindMask::HISTOGRAM` has no effect here.
+    pub fn with_idle_timeout(mut self, mask: MetricKindMask, timeout: Duration) -> Self {
+        self.recency_mask = mask;
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Backs histograms with a streaming quantile sketch instead of a full `hdrhistogram`.
+    ///
+    /// Each observed value is fed into a streaming summary ([`metrics_util::Summary`]) which keeps
+    /// relative-error bucket counts rather than the complete distribution, retaining at most
+    /// `SUMMARY_MAX_BUCKETS` buckets per key.  This is the preferred backend for long-running
+    /// processes that observe many distinct histogram keys.  The configured quantiles are queried from the sketch
+    /// at render time; `error` is the sketch's relative accuracy `α` (e.g. `0.01`), trading accuracy
+    /// for memory.  The remaining `Summary::new` parameters (bucket cap and minimum value) use the
+    /// same defaults as [`Summary`]'s own `with_defaults`.
+    pub fn with_summaries(mut self, error: f64) -> Self {
+        self.summary_error = Some(error);
+        self
+    }
+
+    /// Renders histograms as cumulative buckets instead of quantiles.
+    ///
+    /// For each upper bound `le` in the given set, the observer emits a `bucket{le="<bound>"}` line
+    /// carrying the number of recorded samples less than or equal to that bound, followed by a
+    /// `+Inf` bucket equal to the total sample count and a `sum` line.  When `buckets` is empty the
+    /// default set (`0.005` through `10.0`) is used.
+    ///
+    /// Bounds are compared against recorded values as `f64`, so fractional boundaries are honoured
+    /// even though the underlying `hdrhistogram` stores integer samples.  The `sum` line is a
+    /// lower-bound estimate (see `hist_sum_estimate`) rather than an exact total.
+    ///
+    /// This is configured in parallel to [`TextBuilder::with_quantiles`]; when buckets are set they
+    /// take precedence over the configured quantiles when rendering histograms.  Histograms backed
+    /// by [`TextBuilder::with_summaries`] ignore the buckets and are always rendered as quantiles.
+    pub fn with_buckets(mut self, buckets: &[f64]) -> Self {
+        let buckets = if buckets.is_empty() {
+            DEFAULT_BUCKETS.to_vec()
+        } else {
+            buckets.to_vec()
+        };
+        self.buckets = Some(buckets);
+        self
+    }
 }
 
 impl Builder for TextBuilder {
@@ -86,8 +165,15 @@ impl Builder for TextBuilder {
     fn build(&self) -> Self::Output {
         TextObserver {
             quantiles: self.quantiles.clone(),
+            buckets: self.buckets.clone(),
+            summary_error: self.summary_error,
+            idle_timeout: self.idle_timeout,
+            recency_mask: self.recency_mask,
             structure: MetricsTree::with_level(0),
+            counters: HashMap::new(),
+            gauges: HashMap::new(),
             histos: HashMap::new(),
+            summaries: HashMap::new(),
         }
     }
 }
@@ -101,24 +187,61 @@ impl Default for TextBuilder {
 /// Records metrics in a hierarchical, text-based format.
 pub struct TextObserver {
     pub(crate) quantiles: Vec<Quantile>,
+    pub(crate) buckets: Option<Vec<f64>>,
+    pub(crate) summary_error: Option<f64>,
+    pub(crate) idle_timeout: Option<Duration>,
+    pub(crate) recency_mask: MetricKindMask,
     pub(crate) structure: MetricsTree,
+    pub(crate) counters: HashMap<Key, (u64, Instant)>,
+    pub(crate) gauges: HashMap<Key, (i64, Instant)>,
     pub(crate) histos: HashMap<Key, Histogram<u64>>,
+    pub(crate) summaries: HashMap<Key, Summary>,
 }
 
 impl Observer for TextObserver {
     fn observe_counter(&mut self, key: Key, value: u64) {
+        if self.idle_timeout.is_some() {
+            let entry = self.counters.entry(key).or_insert((value, Instant::now()));
+            if entry.0 != value {
+                *entry = (value, Instant::now());
+            }
+            return;
+        }
+
         let (name_parts, name) = key_to_parts(key);
         let mut values = single_value_to_values(name, value);
         self.structure.insert(name_parts, &mut values);
     }
 
     fn observe_gauge(&mut self, key: Key, value: i64) {
+        if self.idle_timeout.is_some() {
+            let entry = self.gauges.entry(key).or_insert((value, Instant::now()));
+            if entry.0 != value {
+                *entry = (value, Instant::now());
+            }
+            return;
+        }
+
         let (name_parts, name) = key_to_parts(key);
         let mut values = single_value_to_values(name, value);
         self.structure.insert(name_parts, &mut values);
     }
 
     fn observe_histogram(&mut self, key: Key, values: &[u64]) {
+        if let Some(error) = self.summary_error {
+            let entry = self
+                .summaries
+                .entry(key)
+                .or_insert_with(|| {
+                    Summary::new(error, SUMMARY_MAX_BUCKETS, SUMMARY_MIN_VALUE)
+                });
+
+            for value in values {
+                entry.add(*value as f64);
+            }
+            return;
+        }
+
         let entry = self
             .histos
             .entry(key)
@@ -206,15 +329,276 @@ impl MetricsTree {
 
 impl Drain<String> for TextObserver {
     fn drain(&mut self) -> String {
+        if let Some(timeout) = self.idle_timeout {
+            let now = Instant::now();
+
+            let drop_counters = self.recency_mask.matches(MetricKind::Counter);
+            let mut stale = Vec::new();
+            for (key, (value, last_changed)) in self.counters.iter() {
+                if drop_counters && now.saturating_duration_since(*last_changed) >= timeout {
+                    stale.push(key.clone());
+                    continue;
+                }
+                let (name_parts, name) = key_to_parts(key.clone());
+                let mut values = single_value_to_values(name, *value);
+                self.structure.insert(name_parts, &mut values);
+            }
+            for key in stale {
+                self.counters.remove(&key);
+            }
+
+            let drop_gauges = self.recency_mask.matches(MetricKind::Gauge);
+            let mut stale = Vec::new();
+            for (key, (value, last_changed)) in self.gauges.iter() {
+                if drop_gauges && now.saturating_duration_since(*last_changed) >= timeout {
+                    stale.push(key.clone());
+                    continue;
+                }
+                let (name_parts, name) = key_to_parts(key.clone());
+                let mut values = single_value_to_values(name, *value);
+                self.structure.insert(name_parts, &mut values);
+            }
+            for key in stale {
+                self.gauges.remove(&key);
+            }
+        }
+
         for (key, h) in self.histos.drain() {
             let (name_parts, name) = key_to_parts(key);
-            let mut values = hist_to_values(name, h.clone(), &self.quantiles);
+            let mut values = match &self.buckets {
+                Some(buckets) => hist_to_bucket_values(name, h.clone(), buckets),
+                None => hist_to_values(name, h.clone(), &self.quantiles),
+            };
+            self.structure.insert(name_parts, &mut values);
+        }
+        for (key, summary) in self.summaries.drain() {
+            let (name_parts, name) = key_to_parts(key);
+            let mut values = summary_to_values(name, &summary, &self.quantiles);
             self.structure.insert(name_parts, &mut values);
         }
         self.structure.render()
     }
 }
 
+/// Builder for [`PrometheusObserver`].
+pub struct PrometheusBuilder {
+    quantiles: Vec<Quantile>,
+}
+
+impl PrometheusBuilder {
+    /// Creates a new [`PrometheusBuilder`] with a default set of quantiles.
+    ///
+    /// Configures the observer with these default quantiles: 0.0, 0.5, 0.9, 0.95, 0.99, 0.999, and
+    /// 1.0.  If you want to customize the quantiles used, you can call
+    /// [`PrometheusBuilder::with_quantiles`].
+    ///
+    /// The configured quantiles are used when rendering histograms as Prometheus summaries.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`PrometheusBuilder`] with the given set of quantiles.
+    ///
+    /// The configured quantiles are used when rendering histograms as Prometheus summaries.
+    pub fn with_quantiles(quantiles: &[f64]) -> Self {
+        Self {
+            quantiles: parse_quantiles(quantiles),
+        }
+    }
+}
+
+impl Builder for PrometheusBuilder {
+    type Output = PrometheusObserver;
+
+    fn build(&self) -> Self::Output {
+        PrometheusObserver {
+            quantiles: self.quantiles.clone(),
+            counters: HashMap::new(),
+            gauges: HashMap::new(),
+            histos: HashMap::new(),
+        }
+    }
+}
+
+impl Default for PrometheusBuilder {
+    fn default() -> Self {
+        Self::with_quantiles(&[0.0, 0.5, 0.9, 0.95, 0.99, 0.999, 1.0])
+    }
+}
+
+/// Records metrics in the Prometheus text exposition format.
+///
+/// Unlike [`TextObserver`], dots in a metric name do not drive a hierarchy; they are mapped to
+/// underscores so that names are legal Prometheus identifiers.  Counters and gauges are emitted
+/// with a leading `# TYPE` line, and each histogram is rendered as a Prometheus summary with one
+/// line per configured quantile alongside `_sum` and `_count` lines.
+///
+/// Because an `hdrhistogram` only retains bucketed values, the emitted `_sum` is a lower-bound
+/// estimate (the sum of the lowest value equivalent to each recorded sample), not the exact total;
+/// scrapers should not treat it as precise.
+pub struct PrometheusObserver {
+    pub(crate) quantiles: Vec<Quantile>,
+    pub(crate) counters: HashMap<String, Vec<(Vec<(String, String)>, u64)>>,
+    pub(crate) gauges: HashMap<String, Vec<(Vec<(String, String)>, i64)>>,
+    pub(crate) histos: HashMap<Key, Histogram<u64>>,
+}
+
+impl Observer for PrometheusObserver {
+    fn observe_counter(&mut self, key: Key, value: u64) {
+        let (name, pairs) = key_to_prometheus_parts(key);
+        self.counters.entry(name).or_default().push((pairs, value));
+    }
+
+    fn observe_gauge(&mut self, key: Key, value: i64) {
+        let (name, pairs) = key_to_prometheus_parts(key);
+        self.gauges.entry(name).or_default().push((pairs, value));
+    }
+
+    fn observe_histogram(&mut self, key: Key, values: &[u64]) {
+        let entry = self
+            .histos
+            .entry(key)
+            .or_insert_with(|| Histogram::<u64>::new(3).expect("failed to create histogram"));
+
+        for value in values {
+            entry
+                .record(*value)
+                .expect("failed to observe histogram value");
+        }
+    }
+}
+
+impl Drain<String> for PrometheusObserver {
+    fn drain(&mut self) -> String {
+        let mut output = String::new();
+
+        let mut counters = self.counters.drain().collect::<Vec<_>>();
+        counters.sort_by(|a, b| a.0.cmp(&b.0));
+        for (name, series) in counters {
+            output.push_str(&format!("# TYPE {} counter\n", name));
+            for (pairs, value) in series {
+                output.push_str(&format!("{}{} {}\n", name, format_label_set(&pairs), value));
+            }
+        }
+
+        let mut gauges = self.gauges.drain().collect::<Vec<_>>();
+        gauges.sort_by(|a, b| a.0.cmp(&b.0));
+        for (name, series) in gauges {
+            output.push_str(&format!("# TYPE {} gauge\n", name));
+            for (pairs, value) in series {
+                output.push_str(&format!("{}{} {}\n", name, format_label_set(&pairs), value));
+            }
+        }
+
+        let mut histos: HashMap<String, Vec<(Vec<(String, String)>, Histogram<u64>)>> =
+            HashMap::new();
+        for (key, hist) in self.histos.drain() {
+            let (name, pairs) = key_to_prometheus_parts(key);
+            histos.entry(name).or_default().push((pairs, hist));
+        }
+        let mut histos = histos.drain().collect::<Vec<_>>();
+        histos.sort_by(|a, b| a.0.cmp(&b.0));
+        for (name, series) in histos {
+            output.push_str(&format!("# TYPE {} summary\n", name));
+            for (pairs, hist) in series {
+                for quantile in &self.quantiles {
+                    let mut qpairs = pairs.clone();
+                    qpairs.push(("quantile".to_owned(), quantile.value().to_string()));
+                    let value = hist.value_at_quantile(quantile.value());
+                    output.push_str(&format!(
+                        "{}{} {}\n",
+                        name,
+                        format_label_set(&qpairs),
+                        value
+                    ));
+                }
+                output.push_str(&format!(
+                    "{}_sum{} {}\n",
+                    name,
+                    format_label_set(&pairs),
+                    hist_sum_estimate(&hist)
+                ));
+                output.push_str(&format!(
+                    "{}_count{} {}\n",
+                    name,
+                    format_label_set(&pairs),
+                    hist.len()
+                ));
+            }
+        }
+
+        output
+    }
+}
+
+/// Exporter that periodically drains an observer and emits the rendered snapshot via the `log`
+/// crate.
+///
+/// On each tick the exporter asks the `controller` to observe into its owned observer, renders the
+/// collected metrics via [`Drain::drain`], and emits the result with a single `log::log!` call at
+/// the configured level.  This suits environments where stdout isn't scraped but logs are
+/// aggregated, without callers having to drive `drain()` themselves.
+pub struct LogExporter<C, B>
+where
+    B: Builder,
+{
+    controller: C,
+    observer: B::Output,
+    level: Level,
+    interval: Duration,
+}
+
+impl<C, B> LogExporter<C, B>
+where
+    B: Builder,
+    B::Output: Drain<String> + Observer,
+    C: Observe,
+{
+    /// Creates a new [`LogExporter`] that logs at the given level on the given interval.
+    ///
+    /// The observer is built from `builder`, typically a [`TextBuilder`].
+    pub fn new(controller: C, builder: B, level: Level, interval: Duration) -> Self {
+        LogExporter {
+            controller,
+            observer: builder.build(),
+            level,
+            interval,
+        }
+    }
+
+    /// Runs the exporter, sleeping for the configured interval before each snapshot.
+    ///
+    /// This blocks the current thread forever; use [`LogExporter::spawn`] to drive it from a
+    /// dedicated background thread instead.
+    pub fn run(&mut self) {
+        loop {
+            thread::sleep(self.interval);
+            self.turn();
+        }
+    }
+
+    /// Collects and logs a single snapshot.
+    pub fn turn(&mut self) {
+        self.controller.observe(&mut self.observer);
+        let output = self.observer.drain();
+        log::log!(self.level, "{}", output);
+    }
+}
+
+impl<C, B> LogExporter<C, B>
+where
+    B: Builder,
+    B::Output: Drain<String> + Observer + Send + 'static,
+    C: Observe + Send + 'static,
+{
+    /// Spawns a background thread that drives the exporter on its interval.
+    ///
+    /// Returns the [`JoinHandle`](std::thread::JoinHandle) for the spawned thread.
+    pub fn spawn(mut self) -> thread::JoinHandle<()> {
+        thread::spawn(move || self.run())
+    }
+}
+
 enum SortEntry {
     Inline(String),
     Nested(String, MetricsTree),
@@ -257,21 +641,30 @@ fn key_to_parts(key: Key) -> (VecDeque<String>, String) {
         .collect::<VecDeque<_>>();
     let name = parts.pop_back().expect("name didn't have a single part");
 
-    let labels = labels
-        .into_iter()
-        .map(Label::into_parts)
-        .map(|(k, v)| format!("{}=\"{}\"", k, v))
-        .collect::<Vec<_>>()
-        .join(",");
-    let label = if labels.is_empty() {
+    let pairs = labels.into_iter().map(Label::into_parts).collect::<Vec<_>>();
+    let fname = format!("{}{}", name, format_label_set(&pairs));
+
+    (parts, fname)
+}
+
+fn key_to_prometheus_parts(key: Key) -> (String, Vec<(String, String)>) {
+    let (name, labels) = key.into_parts();
+    let name = name.replace('.', "_");
+    let pairs = labels.into_iter().map(Label::into_parts).collect::<Vec<_>>();
+    (name, pairs)
+}
+
+fn format_label_set(pairs: &[(String, String)]) -> String {
+    if pairs.is_empty() {
         String::new()
     } else {
+        let labels = pairs
+            .iter()
+            .map(|(k, v)| format!("{}=\"{}\"", k, v))
+            .collect::<Vec<_>>()
+            .join(",");
         format!("{{{}}}", labels)
-    };
-
-    let fname = format!("{}{}", name, label);
-
-    (parts, fname)
+    }
 }
 
 fn single_value_to_values<T>(name: String, value: T) -> Vec<String>
@@ -292,4 +685,95 @@ fn hist_to_values(name: String, hist: Histogram<u64>, quantiles: &[Quantile]) ->
     }
 
     values
-}
\ No newline at end of file
+}
+
+/// Lower-bound estimate of the sum of all recorded samples.
+///
+/// An `hdrhistogram` stores values bucketed into equivalent ranges and cannot reconstruct the exact
+/// total, so each sample is counted at the lowest value equivalent to its bucket.  This avoids the
+/// systematic overestimate that summing the top-of-range value would produce.
+fn hist_sum_estimate(hist: &Histogram<u64>) -> u64 {
+    hist.iter_recorded()
+        .map(|v| hist.lowest_equivalent(v.value_iterated_to()) * v.count_at_value())
+        .sum()
+}
+
+fn summary_to_values(name: String, summary: &Summary, quantiles: &[Quantile]) -> Vec<String> {
+    let mut values = Vec::new();
+
+    values.push(format!("{} count: {}", name, summary.count()));
+    for quantile in quantiles {
+        let value = summary.quantile(quantile.value()).unwrap_or(0.0);
+        values.push(format!("{} {}: {}", name, quantile.label(), value));
+    }
+
+    values
+}
+
+fn hist_to_bucket_values(name: String, hist: Histogram<u64>, buckets: &[f64]) -> Vec<String> {
+    let mut values = Vec::new();
+
+    for bound in buckets {
+        // `hdrhistogram<u64>` can only be queried on integer boundaries, so comparing each recorded
+        // value against the (possibly fractional) bound directly is the only way to keep the
+        // emitted `le` label honest — truncating the bound to `u64` would collapse every sub-integer
+        // boundary to zero.
+        let count: u64 = hist
+            .iter_recorded()
+            .filter(|v| v.value_iterated_to() as f64 <= *bound)
+            .map(|v| v.count_at_value())
+            .sum();
+        values.push(format!("{} bucket{{le=\"{}\"}}: {}", name, bound, count));
+    }
+
+    values.push(format!("{} bucket{{le=\"+Inf\"}}: {}", name, hist.len()));
+
+    values.push(format!("{} sum: {}", name, hist_sum_estimate(&hist)));
+
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn histogram(values: &[u64]) -> Histogram<u64> {
+        let mut hist = Histogram::<u64>::new(3).expect("failed to create histogram");
+        for value in values {
+            hist.record(*value).expect("failed to record value");
+        }
+        hist
+    }
+
+    #[test]
+    fn bucket_values_count_samples_at_or_below_each_bound() {
+        let hist = histogram(&[1, 2, 3, 10]);
+        let values = hist_to_bucket_values("latency".to_owned(), hist, &[1.0, 5.0]);
+
+        assert_eq!(
+            values,
+            vec![
+                "latency bucket{le=\"1\"}: 1".to_owned(),
+                "latency bucket{le=\"5\"}: 3".to_owned(),
+                "latency bucket{le=\"+Inf\"}: 4".to_owned(),
+                "latency sum: 16".to_owned(),
+            ]
+        );
+    }
+
+    #[test]
+    fn bucket_values_honour_fractional_bounds() {
+        // The `0` sample falls under the fractional bound, the `1` sample does not, so only one
+        // sample is counted.
+        let hist = histogram(&[0, 1]);
+        let values = hist_to_bucket_values("wait".to_owned(), hist, &[0.5]);
+
+        assert_eq!(values[0], "wait bucket{le=\"0.5\"}: 1".to_owned());
+    }
+
+    #[test]
+    fn sum_estimate_is_a_lower_bound() {
+        let hist = histogram(&[1, 2, 3]);
+        assert_eq!(hist_sum_estimate(&hist), 6);
+    }
+}